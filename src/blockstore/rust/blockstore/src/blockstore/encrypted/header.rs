@@ -0,0 +1,129 @@
+use anyhow::{bail, Result};
+
+use crate::crypto::symmetric::CipherSuite;
+
+/// Declares the block header's binary layout: each field's byte offset and width is fixed at
+/// compile time, so adding a field (e.g. a per-block nonce or KDF salt) is a matter of adding one
+/// more `field!` line and everything downstream -- [Header::LEN], the getters/setters, and
+/// [super::EncryptedBlockStore]'s prefix reservation -- follows without any hand counting.
+macro_rules! fields {
+    ($($name:ident : $ty:ty = $offset:expr, $size:expr;)*) => {
+        $(
+            #[allow(dead_code)]
+            const $name: (usize, usize) = ($offset, $size);
+        )*
+
+        /// Total width of the header, in bytes.
+        pub const LEN: usize = fields!(@last $($offset, $size;)*);
+    };
+    (@last $offset:expr, $size:expr;) => { $offset + $size };
+    (@last $offset:expr, $size:expr; $($rest:tt)+) => { fields!(@last $($rest)+) };
+}
+
+fields! {
+    VERSION: u16 = 0, 2;
+    ALGORITHM_ID: u16 = 2, 2;
+}
+
+const FORMAT_VERSION: u16 = 2;
+
+/// A read view over a block's header, backed by the first [LEN] bytes of the block.
+pub struct HeaderView<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> HeaderView<'a> {
+    /// Parses the header out of `bytes`, which must be at least [LEN] bytes long. Rejects an
+    /// unknown `version` instead of letting a later field misalign.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        if bytes.len() < LEN {
+            bail!(
+                "Couldn't parse encrypted block. Expected a header of at least {} bytes but block only has {}.",
+                LEN,
+                bytes.len(),
+            );
+        }
+        let header = Self { bytes };
+        let version = header.version();
+        if version != FORMAT_VERSION {
+            bail!(
+                "Couldn't parse encrypted block. Expected FORMAT_VERSION of {} but found {}",
+                FORMAT_VERSION,
+                version,
+            );
+        }
+        Ok(header)
+    }
+
+    pub fn algorithm_id(&self) -> u16 {
+        self.read_u16(ALGORITHM_ID)
+    }
+
+    pub fn cipher_suite(&self) -> Result<CipherSuite> {
+        CipherSuite::from_algorithm_id(self.algorithm_id())
+    }
+
+    fn version(&self) -> u16 {
+        self.read_u16(VERSION)
+    }
+
+    fn read_u16(&self, (offset, size): (usize, usize)) -> u16 {
+        u16::from_ne_bytes(self.bytes[offset..offset + size].try_into().unwrap())
+    }
+}
+
+/// A write view over a block's header, backed by the first [LEN] bytes of the block. Writing
+/// through this type is the only place that is allowed to know the header's byte layout.
+pub struct HeaderViewMut<'a> {
+    bytes: &'a mut [u8],
+}
+
+impl<'a> HeaderViewMut<'a> {
+    /// `bytes` must be at least [LEN] bytes long.
+    pub fn new(bytes: &'a mut [u8]) -> Self {
+        assert!(bytes.len() >= LEN, "HeaderViewMut needs at least {} bytes", LEN);
+        Self { bytes }
+    }
+
+    pub fn set_cipher_suite(&mut self, suite: CipherSuite) -> &mut Self {
+        self.write_u16(VERSION, FORMAT_VERSION);
+        self.write_u16(ALGORITHM_ID, suite.algorithm_id());
+        self
+    }
+
+    fn write_u16(&mut self, (offset, size): (usize, usize), value: u16) {
+        self.bytes[offset..offset + size].copy_from_slice(&value.to_ne_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_a_buffer_shorter_than_the_header() {
+        let buf = vec![0u8; LEN - 1];
+        assert!(HeaderView::parse(&buf).is_err());
+    }
+
+    #[test]
+    fn parse_rejects_an_unknown_format_version() {
+        let mut buf = vec![0u8; LEN];
+        HeaderViewMut::new(&mut buf).set_cipher_suite(CipherSuite::Aes128Gcm);
+        buf[VERSION.0..VERSION.0 + VERSION.1].copy_from_slice(&(FORMAT_VERSION + 1).to_ne_bytes());
+        assert!(HeaderView::parse(&buf).is_err());
+    }
+
+    #[test]
+    fn parse_recovers_the_cipher_suite_that_set_cipher_suite_wrote() {
+        for suite in [
+            CipherSuite::Aes128Gcm,
+            CipherSuite::Aes256Gcm,
+            CipherSuite::XChaCha20Poly1305,
+        ] {
+            let mut buf = vec![0u8; LEN];
+            HeaderViewMut::new(&mut buf).set_cipher_suite(suite);
+            assert_eq!(HeaderView::parse(&buf).unwrap().cipher_suite().unwrap(), suite);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{bail, Result};
+
+use super::{Aes128Gcm, Aes256Gcm, Cipher, XChaCha20Poly1305};
+
+/// Identifies which [Cipher] algorithm a block was encrypted with. This is the value stored in
+/// the `algorithm_id` field of the block header, so that blocks belonging to the same file
+/// system can each be encrypted with a different algorithm (e.g. while an online re-encryption
+/// migrates a file system from one suite to another, see [super::MultiCipher]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherSuite {
+    Aes128Gcm,
+    Aes256Gcm,
+    XChaCha20Poly1305,
+}
+
+impl CipherSuite {
+    pub const fn algorithm_id(self) -> u16 {
+        match self {
+            Self::Aes128Gcm => 1,
+            Self::Aes256Gcm => 2,
+            Self::XChaCha20Poly1305 => 3,
+        }
+    }
+
+    pub fn from_algorithm_id(algorithm_id: u16) -> Result<Self> {
+        match algorithm_id {
+            1 => Ok(Self::Aes128Gcm),
+            2 => Ok(Self::Aes256Gcm),
+            3 => Ok(Self::XChaCha20Poly1305),
+            _ => bail!("Unknown cipher suite algorithm id {}", algorithm_id),
+        }
+    }
+
+    pub const fn ciphertext_overhead(self) -> usize {
+        match self {
+            Self::Aes128Gcm => Aes128Gcm::CIPHERTEXT_OVERHEAD,
+            Self::Aes256Gcm => Aes256Gcm::CIPHERTEXT_OVERHEAD,
+            Self::XChaCha20Poly1305 => XChaCha20Poly1305::CIPHERTEXT_OVERHEAD,
+        }
+    }
+
+    /// The largest [Self::ciphertext_overhead] among all variants. Code that doesn't yet know
+    /// (or doesn't want to commit to) which suite a block will end up using, e.g. when
+    /// allocating a fresh block, must reserve this much prefix space so that any suite can be
+    /// picked later without a reallocation.
+    pub const fn max_ciphertext_overhead() -> usize {
+        const_max(
+            const_max(Aes128Gcm::CIPHERTEXT_OVERHEAD, Aes256Gcm::CIPHERTEXT_OVERHEAD),
+            XChaCha20Poly1305::CIPHERTEXT_OVERHEAD,
+        )
+    }
+}
+
+const fn const_max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [CipherSuite; 3] = [
+        CipherSuite::Aes128Gcm,
+        CipherSuite::Aes256Gcm,
+        CipherSuite::XChaCha20Poly1305,
+    ];
+
+    #[test]
+    fn algorithm_id_round_trips_through_from_algorithm_id() {
+        for suite in ALL {
+            assert_eq!(
+                CipherSuite::from_algorithm_id(suite.algorithm_id()).unwrap(),
+                suite,
+            );
+        }
+    }
+
+    #[test]
+    fn from_algorithm_id_rejects_an_unknown_id() {
+        assert!(CipherSuite::from_algorithm_id(0).is_err());
+        assert!(CipherSuite::from_algorithm_id(4).is_err());
+    }
+
+    #[test]
+    fn max_ciphertext_overhead_is_at_least_every_suites_overhead() {
+        for suite in ALL {
+            assert!(suite.ciphertext_overhead() <= CipherSuite::max_ciphertext_overhead());
+        }
+    }
+}
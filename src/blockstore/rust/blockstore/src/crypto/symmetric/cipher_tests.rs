@@ -0,0 +1,45 @@
+use generic_array::GenericArray;
+use std::convert::TryInto;
+
+use super::{Aes128Gcm, Cipher, EncryptionKey};
+use crate::data::{Data, GrowableData};
+
+fn cipher() -> Aes128Gcm {
+    Aes128Gcm::new(EncryptionKey::from_array(GenericArray::default()))
+}
+
+fn plaintext(bytes: &[u8]) -> GrowableData<{ Aes128Gcm::CIPHERTEXT_OVERHEAD }, 0> {
+    let mut data: GrowableData<{ Aes128Gcm::CIPHERTEXT_OVERHEAD }, 0> =
+        Data::from(vec![0; Aes128Gcm::CIPHERTEXT_OVERHEAD + bytes.len()])
+            .into_subregion(Aes128Gcm::CIPHERTEXT_OVERHEAD..)
+            .try_into()
+            .unwrap();
+    data.as_mut().copy_from_slice(bytes);
+    data
+}
+
+#[test]
+fn decrypting_under_the_associated_data_it_was_encrypted_with_succeeds() {
+    let cipher = cipher();
+    let ciphertext = cipher
+        .encrypt(plaintext(b"hello world"), b"block-a")
+        .unwrap();
+
+    let decrypted = cipher.decrypt(ciphertext.extract(), b"block-a").unwrap();
+
+    assert_eq!(decrypted.as_ref(), b"hello world");
+}
+
+#[test]
+fn decrypting_under_a_different_associated_data_fails() {
+    let cipher = cipher();
+    let ciphertext = cipher
+        .encrypt(plaintext(b"hello world"), b"block-a")
+        .unwrap();
+
+    // A ciphertext relocated to (or read back under) a different BlockId must not decrypt, or
+    // an attacker with storage access could swap blocks between ids undetected.
+    let result = cipher.decrypt(ciphertext.extract(), b"block-b");
+
+    assert!(result.is_err());
+}
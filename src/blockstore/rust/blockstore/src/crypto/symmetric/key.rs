@@ -0,0 +1,33 @@
+use generic_array::{ArrayLength, GenericArray};
+
+/// Raw symmetric key material for a [Cipher](super::Cipher). Scrubbed with a volatile zero write
+/// when dropped, the same way [Data](crate::data::Data)'s backing buffer is, so a key doesn't
+/// linger in freed heap memory once its cipher is no longer needed.
+pub struct EncryptionKey<KeySize: ArrayLength<u8>> {
+    key: GenericArray<u8, KeySize>,
+}
+
+impl<KeySize: ArrayLength<u8>> EncryptionKey<KeySize> {
+    pub fn from_array(key: GenericArray<u8, KeySize>) -> Self {
+        Self { key }
+    }
+}
+
+impl<KeySize: ArrayLength<u8>> AsRef<[u8]> for EncryptionKey<KeySize> {
+    fn as_ref(&self) -> &[u8] {
+        self.key.as_ref()
+    }
+}
+
+impl<KeySize: ArrayLength<u8>> Drop for EncryptionKey<KeySize> {
+    fn drop(&mut self) {
+        for byte in self.key.iter_mut() {
+            // SAFETY: `byte` is a valid, aligned, writable reference to a `u8` for the duration
+            // of this call.
+            unsafe {
+                std::ptr::write_volatile(byte, 0);
+            }
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
@@ -12,12 +12,17 @@ pub trait Cipher: Sized {
     // How many bytes is a ciphertext larger than a plaintext?
     const CIPHERTEXT_OVERHEAD: usize;
 
+    /// `associated_data` is authenticated by the AEAD tag but not encrypted. Callers should pass
+    /// something that identifies where this ciphertext is allowed to live (e.g. the `BlockId` it
+    /// is stored under), so that `decrypt` fails if the ciphertext is ever moved elsewhere.
     fn encrypt<const PREFIX_BYTES: usize>(
         &self,
         data: GrowableData<PREFIX_BYTES, 0>,
+        associated_data: &[u8],
     ) -> Result<GrowableData<{ PREFIX_BYTES - Self::CIPHERTEXT_OVERHEAD }, 0>>;
 
-    fn decrypt(&self, data: Data) -> Result<Data>;
+    /// `associated_data` must be exactly what was passed to `encrypt`, or decryption fails.
+    fn decrypt(&self, data: Data, associated_data: &[u8]) -> Result<Data>;
 }
 
 fn encrypt<const PrefixBytes: usize>(
@@ -31,7 +36,9 @@ fn encrypt<const PrefixBytes: usize>(
 mod aead_crate_wrapper;
 mod aesgcm;
 mod cipher_crate_wrapper;
+mod cipher_suite;
 mod key;
+mod multi_cipher;
 
 #[cfg(test)]
 mod cipher_tests;
@@ -41,3 +48,6 @@ pub use key::EncryptionKey;
 // export ciphers
 pub use aesgcm::{Aes128Gcm, Aes256Gcm};
 pub type XChaCha20Poly1305 = aead_crate_wrapper::AeadCipher<chacha20poly1305::XChaCha20Poly1305>;
+
+pub use cipher_suite::CipherSuite;
+pub use multi_cipher::MultiCipher;
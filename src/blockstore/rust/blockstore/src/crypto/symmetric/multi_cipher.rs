@@ -0,0 +1,189 @@
+use anyhow::{ensure, Result};
+
+use super::{Aes128Gcm, Aes256Gcm, Cipher, CipherSuite, XChaCha20Poly1305};
+use crate::data::{Data, GrowableData};
+
+/// Holds a keyed [Cipher] instance for every [CipherSuite] variant and encrypts new blocks with
+/// a configured `primary` suite, while remaining able to decrypt blocks that were written under
+/// any of the other suites. This is what lets a single file system hold blocks encrypted with
+/// different algorithms at the same time, e.g. while [EncryptedBlockStore::reencrypt] is
+/// migrating a file system from one suite to another.
+pub struct MultiCipher {
+    primary: CipherSuite,
+    aes128gcm: Aes128Gcm,
+    aes256gcm: Aes256Gcm,
+    xchacha20poly1305: XChaCha20Poly1305,
+}
+
+impl MultiCipher {
+    pub fn new(
+        primary: CipherSuite,
+        aes128gcm: Aes128Gcm,
+        aes256gcm: Aes256Gcm,
+        xchacha20poly1305: XChaCha20Poly1305,
+    ) -> Self {
+        Self {
+            primary,
+            aes128gcm,
+            aes256gcm,
+            xchacha20poly1305,
+        }
+    }
+
+    pub fn primary_suite(&self) -> CipherSuite {
+        self.primary
+    }
+
+    /// Encrypts `plaintext` with the configured primary suite. The caller must have reserved
+    /// [CipherSuite::max_ciphertext_overhead] prefix bytes since the actual suite used here is
+    /// only known at runtime; any slack between the reservation and what the primary suite
+    /// actually needs is trimmed off before returning.
+    ///
+    /// `associated_data` is authenticated but not encrypted; callers must pass the same bytes to
+    /// [Self::decrypt] or decryption will fail. This is how `EncryptedBlockStore` binds a
+    /// ciphertext to the `BlockId` it's stored under.
+    pub fn encrypt<const PREFIX_BYTES: usize>(
+        &self,
+        plaintext: GrowableData<PREFIX_BYTES, 0>,
+        associated_data: &[u8],
+    ) -> Result<(CipherSuite, Data)> {
+        // Ciphertext isn't a secret, so it's marked unscrubbed before being handed back: without
+        // this, the buffer would still pay for a zeroizing wipe on every block write/read even
+        // though there's nothing left in it worth scrubbing.
+        //
+        // `slack` is the gap between what the worst-case suite would have needed (what callers
+        // reserved) and what this suite actually used. It's absorbed into the visible region
+        // (rather than discarded) so that every suite's result has the *same* available prefix
+        // bytes left afterwards, regardless of which suite was picked -- callers downstream of
+        // `encrypt` size their own header by that constant, not by a per-suite overhead.
+        match self.primary {
+            CipherSuite::Aes128Gcm => {
+                let ciphertext = self.aes128gcm.encrypt(plaintext, associated_data)?;
+                let slack = CipherSuite::max_ciphertext_overhead() - Aes128Gcm::CIPHERTEXT_OVERHEAD;
+                Ok((
+                    CipherSuite::Aes128Gcm,
+                    ciphertext.into_unscrubbed().extract().grow_region(slack, 0)?,
+                ))
+            }
+            CipherSuite::Aes256Gcm => {
+                let ciphertext = self.aes256gcm.encrypt(plaintext, associated_data)?;
+                let slack = CipherSuite::max_ciphertext_overhead() - Aes256Gcm::CIPHERTEXT_OVERHEAD;
+                Ok((
+                    CipherSuite::Aes256Gcm,
+                    ciphertext.into_unscrubbed().extract().grow_region(slack, 0)?,
+                ))
+            }
+            CipherSuite::XChaCha20Poly1305 => {
+                let ciphertext = self.xchacha20poly1305.encrypt(plaintext, associated_data)?;
+                let slack =
+                    CipherSuite::max_ciphertext_overhead() - XChaCha20Poly1305::CIPHERTEXT_OVERHEAD;
+                Ok((
+                    CipherSuite::XChaCha20Poly1305,
+                    ciphertext.into_unscrubbed().extract().grow_region(slack, 0)?,
+                ))
+            }
+        }
+    }
+
+    /// Decrypts `ciphertext` with whichever keyed [Cipher] matches `suite`, regardless of
+    /// whether `suite` is the configured primary. `suite` normally comes from the algorithm id
+    /// recorded in the block's header. `associated_data` must match what was passed to
+    /// [Self::encrypt], e.g. the `BlockId` the ciphertext is being loaded from; a mismatch (such
+    /// as a ciphertext that was relocated to a different id) fails the AEAD tag check.
+    pub fn decrypt(&self, suite: CipherSuite, ciphertext: Data, associated_data: &[u8]) -> Result<Data> {
+        // Mirror image of the slack absorbed in `Self::encrypt`: `ciphertext` still carries
+        // `max_ciphertext_overhead() - suite.ciphertext_overhead()` unused bytes ahead of what
+        // `suite`'s own cipher actually produced, so those need to be cut back off before handing
+        // it to that cipher.
+        let slack = CipherSuite::max_ciphertext_overhead() - suite.ciphertext_overhead();
+        ensure!(
+            ciphertext.len() >= slack,
+            "Couldn't parse encrypted block. Expected at least {} bytes of ciphertext for {:?} but only found {}.",
+            slack,
+            suite,
+            ciphertext.len(),
+        );
+        let ciphertext = ciphertext.into_subregion(slack..);
+        match suite {
+            CipherSuite::Aes128Gcm => self.aes128gcm.decrypt(ciphertext, associated_data),
+            CipherSuite::Aes256Gcm => self.aes256gcm.decrypt(ciphertext, associated_data),
+            CipherSuite::XChaCha20Poly1305 => {
+                self.xchacha20poly1305.decrypt(ciphertext, associated_data)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::symmetric::EncryptionKey;
+    use generic_array::GenericArray;
+    use std::convert::TryInto;
+
+    const SUITES: [CipherSuite; 3] = [
+        CipherSuite::Aes128Gcm,
+        CipherSuite::Aes256Gcm,
+        CipherSuite::XChaCha20Poly1305,
+    ];
+
+    fn multi_cipher(primary: CipherSuite) -> MultiCipher {
+        MultiCipher::new(
+            primary,
+            Aes128Gcm::new(EncryptionKey::from_array(GenericArray::default())),
+            Aes256Gcm::new(EncryptionKey::from_array(GenericArray::default())),
+            XChaCha20Poly1305::new(EncryptionKey::from_array(GenericArray::default())),
+        )
+    }
+
+    fn plaintext(
+        bytes: &[u8],
+    ) -> GrowableData<{ CipherSuite::max_ciphertext_overhead() }, 0> {
+        let mut data: GrowableData<{ CipherSuite::max_ciphertext_overhead() }, 0> =
+            Data::from(vec![0; CipherSuite::max_ciphertext_overhead() + bytes.len()])
+                .into_subregion(CipherSuite::max_ciphertext_overhead()..)
+                .try_into()
+                .unwrap();
+        data.as_mut().copy_from_slice(bytes);
+        data
+    }
+
+    #[test]
+    fn each_suite_round_trips_when_used_as_primary() {
+        for primary in SUITES {
+            let cipher = multi_cipher(primary);
+            let (suite, ciphertext) = cipher
+                .encrypt(plaintext(b"hello world"), b"block-a")
+                .unwrap();
+            assert_eq!(suite, primary);
+
+            // Every suite must leave the same amount of available prefix capacity behind,
+            // regardless of how much less than the worst case its own overhead actually needed --
+            // this is the slack accounting that callers downstream of `encrypt` (e.g.
+            // `_prepend_header`) rely on.
+            assert_eq!(
+                ciphertext.available_prefix_bytes(),
+                0,
+                "suite {:?} left a different amount of prefix capacity than the worst case reserves",
+                primary,
+            );
+
+            let decrypted = cipher.decrypt(suite, ciphertext, b"block-a").unwrap();
+            assert_eq!(decrypted.as_ref(), b"hello world");
+        }
+    }
+
+    #[test]
+    fn decrypting_a_block_truncated_shorter_than_the_slack_is_an_error_not_a_panic() {
+        // Aes128Gcm isn't the suite with the largest overhead, so encrypting with it as primary
+        // leaves a non-zero amount of slack for `decrypt` to strip back off.
+        let cipher = multi_cipher(CipherSuite::Aes128Gcm);
+        let (suite, ciphertext) = cipher.encrypt(plaintext(b"hi"), b"block-a").unwrap();
+        let slack = CipherSuite::max_ciphertext_overhead() - suite.ciphertext_overhead();
+        assert!(slack > 0, "test assumes Aes128Gcm isn't the max-overhead suite");
+
+        // A block corrupted or truncated down to less than the slack must be rejected, not panic.
+        let truncated = ciphertext.into_subregion(0..(slack - 1));
+        assert!(cipher.decrypt(suite, truncated, b"block-a").is_err());
+    }
+}
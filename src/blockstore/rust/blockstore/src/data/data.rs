@@ -0,0 +1,174 @@
+use anyhow::{ensure, Result};
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
+
+/// An owned byte buffer that remembers how much spare capacity it has before and after its
+/// current, visible region, so that region can grow or shrink in place without reallocating. See
+/// [GrowableData](super::GrowableData) for a variant that tracks that spare capacity at compile
+/// time instead.
+///
+/// Scrubs its storage with a volatile zero write when dropped, since callers (e.g. decrypted
+/// plaintext coming out of `Cipher::decrypt`) often hold a `Data` containing a secret. Buffers
+/// that are known to only ever hold non-secret data (e.g. ciphertext) can opt out of the wipe
+/// with [Self::into_unscrubbed] to avoid paying for it on every block I/O.
+pub struct Data {
+    storage: Vec<u8>,
+    region: std::ops::Range<usize>,
+    secret: bool,
+}
+
+impl Data {
+    pub fn len(&self) -> usize {
+        self.region.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.region.is_empty()
+    }
+
+    pub fn available_prefix_bytes(&self) -> usize {
+        self.region.start
+    }
+
+    pub fn available_suffix_bytes(&self) -> usize {
+        self.storage.len() - self.region.end
+    }
+
+    /// Shrinks the visible region by removing `range`'s complement, i.e. the bytes cut away on
+    /// either side become available prefix/suffix capacity again rather than being deallocated.
+    pub fn into_subregion(mut self, range: impl RangeBounds<usize>) -> Self {
+        let len = self.len();
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        };
+        assert!(
+            start <= end && end <= len,
+            "Tried to take subregion {}..{} of a Data with length {}",
+            start,
+            end,
+            len,
+        );
+        self.region = (self.region.start + start)..(self.region.start + end);
+        self
+    }
+
+    /// Grows the visible region into previously reserved prefix/suffix capacity.
+    pub fn grow_region(mut self, add_prefix_bytes: usize, add_suffix_bytes: usize) -> Result<Self> {
+        ensure!(
+            add_prefix_bytes <= self.available_prefix_bytes(),
+            "Tried to grow a Data's prefix by {} bytes but only {} are available",
+            add_prefix_bytes,
+            self.available_prefix_bytes(),
+        );
+        ensure!(
+            add_suffix_bytes <= self.available_suffix_bytes(),
+            "Tried to grow a Data's suffix by {} bytes but only {} are available",
+            add_suffix_bytes,
+            self.available_suffix_bytes(),
+        );
+        self.region = (self.region.start - add_prefix_bytes)..(self.region.end + add_suffix_bytes);
+        Ok(self)
+    }
+
+    /// Marks this buffer as not containing a secret, e.g. because it holds ciphertext rather
+    /// than plaintext or key material, so it isn't zeroized when dropped.
+    pub fn into_unscrubbed(self) -> Self {
+        self.with_secret(false)
+    }
+
+    /// Overrides whether this buffer is treated as holding a secret, in either direction. Used by
+    /// [GrowableData](super::GrowableData)'s conversions to keep its compile-time `SECRET` marker
+    /// in sync with this runtime flag.
+    pub(crate) fn with_secret(mut self, secret: bool) -> Self {
+        self.secret = secret;
+        self
+    }
+}
+
+impl From<Vec<u8>> for Data {
+    fn from(storage: Vec<u8>) -> Self {
+        let region = 0..storage.len();
+        Self {
+            storage,
+            region,
+            secret: true,
+        }
+    }
+}
+
+impl AsRef<[u8]> for Data {
+    fn as_ref(&self) -> &[u8] {
+        &self.storage[self.region.clone()]
+    }
+}
+
+impl AsMut<[u8]> for Data {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.storage[self.region.clone()]
+    }
+}
+
+impl Deref for Data {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl DerefMut for Data {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.as_mut()
+    }
+}
+
+impl Drop for Data {
+    fn drop(&mut self) {
+        if self.secret {
+            zeroize(&mut self.storage);
+        }
+    }
+}
+
+/// Overwrites `bytes` with zeroes using a volatile write, so the optimizer can't reason the
+/// write away as dead code just because nothing reads `bytes` afterwards.
+fn zeroize(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        // SAFETY: `byte` is a valid, aligned, writable reference to a `u8` for the duration of
+        // this call.
+        unsafe {
+            std::ptr::write_volatile(byte, 0);
+        }
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresh_data_defaults_to_secret() {
+        let data: Data = vec![1, 2, 3].into();
+        assert!(data.secret);
+    }
+
+    #[test]
+    fn into_unscrubbed_marks_the_buffer_as_not_secret() {
+        let data: Data = vec![1, 2, 3].into();
+        assert!(!data.into_unscrubbed().secret);
+    }
+
+    #[test]
+    fn with_secret_can_turn_scrubbing_back_on() {
+        let data: Data = vec![1, 2, 3].into();
+        let unscrubbed = data.into_unscrubbed();
+        assert!(!unscrubbed.secret);
+        assert!(unscrubbed.with_secret(true).secret);
+    }
+}
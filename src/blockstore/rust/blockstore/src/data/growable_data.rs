@@ -8,12 +8,19 @@ use super::data::Data;
 /// how much prefix bytes and suffix bytes are available. This means [GrowableData::grow_region]
 /// will know at compile time if it succeeds and this can be used to write safe APIs that require
 /// data types with a certain number of prefix or suffix bytes and will check that invariant at compile time.
-pub struct GrowableData<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize> {
+///
+/// `SECRET` tracks, at the type level, whether the backing [Data] is zeroized on drop (see
+/// [Self::into_unscrubbed]) -- the actual wipe is [Data]'s job, `GrowableData` just keeps the
+/// backing `Data`'s runtime flag in sync with this type parameter on construction. It defaults
+/// to `true` so that plaintext isn't accidentally left lying around in freed memory; buffers
+/// that are known to only ever hold ciphertext can opt out of the wipe to avoid paying for it on
+/// every block I/O.
+pub struct GrowableData<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize, const SECRET: bool = true> {
     data: Data,
 }
 
-impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize>
-    GrowableData<PREFIX_BYTES, SUFFIX_BYTES>
+impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize, const SECRET: bool>
+    GrowableData<PREFIX_BYTES, SUFFIX_BYTES, SECRET>
 {
     const PREFIX_BYTES: usize = PREFIX_BYTES;
     const SUFFIX_BYTES: usize = SUFFIX_BYTES;
@@ -43,6 +50,7 @@ impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize>
     ) -> GrowableData<
         { PREFIX_BYTES + DELETE_NUM_BYTES_AT_BEGINNING },
         { SUFFIX_BYTES + DELETE_NUM_BYTES_AT_END },
+        SECRET,
     > {
         let len = self.data.len();
         assert!(
@@ -70,6 +78,7 @@ impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize>
     ) -> GrowableData<
         { PREFIX_BYTES - ADD_NUM_BYTES_AT_BEGINNING },
         { SUFFIX_BYTES - ADD_NUM_BYTES_AT_END },
+        SECRET,
     > {
         // const INVARIANT: bool =
         //     GreaterEquals::<{ PREFIX_BYTES }, { ADD_NUM_BYTES_AT_BEGINNING }>::RESULT;
@@ -89,18 +98,27 @@ impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize>
     pub fn extract(self) -> Data {
         self.data
     }
+
+    /// Marks this buffer as not containing a secret, e.g. because it's ciphertext rather than
+    /// plaintext or key material. An unscrubbed buffer is not zeroized when dropped, which saves
+    /// a wipe over every byte of every block written or read.
+    pub fn into_unscrubbed(self) -> GrowableData<PREFIX_BYTES, SUFFIX_BYTES, false> {
+        GrowableData {
+            data: self.data.into_unscrubbed(),
+        }
+    }
 }
 
-impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize> AsRef<[u8]>
-    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES>
+impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize, const SECRET: bool> AsRef<[u8]>
+    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES, SECRET>
 {
     fn as_ref(&self) -> &[u8] {
         self.data.as_ref()
     }
 }
 
-impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize> AsMut<[u8]>
-    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES>
+impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize, const SECRET: bool> AsMut<[u8]>
+    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES, SECRET>
 {
     fn as_mut(&mut self) -> &mut [u8] {
         self.data.as_mut()
@@ -108,8 +126,8 @@ impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize> AsMut<[u8]>
 }
 
 // TODO Test
-impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize> Deref
-    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES>
+impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize, const SECRET: bool> Deref
+    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES, SECRET>
 {
     type Target = [u8];
     fn deref(&self) -> &[u8] {
@@ -118,8 +136,8 @@ impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize> Deref
 }
 
 // TODO Test
-impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize> DerefMut
-    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES>
+impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize, const SECRET: bool> DerefMut
+    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES, SECRET>
 {
     fn deref_mut(&mut self) -> &mut [u8] {
         self.as_mut()
@@ -133,8 +151,8 @@ impl From<Vec<u8>> for GrowableData<0, 0> {
     }
 }
 
-impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize> TryFrom<Data>
-    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES>
+impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize, const SECRET: bool> TryFrom<Data>
+    for GrowableData<PREFIX_BYTES, SUFFIX_BYTES, SECRET>
 {
     // TODO Custom error type
     type Error = Error;
@@ -143,6 +161,7 @@ impl<const PREFIX_BYTES: usize, const SUFFIX_BYTES: usize> TryFrom<Data>
     fn try_from(data: Data) -> Result<Self> {
         ensure!(data.available_prefix_bytes() == PREFIX_BYTES, "The given data object has {} prefix bytes available, but we tried to convert it into a GrowableData requiring {} prefix bytes", data.available_prefix_bytes(), PREFIX_BYTES);
         ensure!(data.available_suffix_bytes() == SUFFIX_BYTES, "The given data object has {} suffix bytes available, but we tried to convert it into a GrowableData requiring {} suffix bytes", data.available_suffix_bytes(), SUFFIX_BYTES);
+        let data = data.with_secret(SECRET);
         Ok(Self { data })
     }
 }
@@ -297,4 +316,24 @@ mod tests {
                 [..=650][10..600][3..=500]
         );
     }
+
+    #[test]
+    fn unscrubbed_buffer_can_still_be_read_after_conversion() {
+        let data: GrowableData<0, 0> = data_region(64, 0).into();
+        let unscrubbed = data.into_unscrubbed();
+        assert_eq!(unscrubbed.as_ref(), &data_region(64, 0));
+    }
+
+    #[test]
+    fn try_from_data_converts_in_both_secret_directions_without_losing_content() {
+        // SECRET = false: turns scrubbing off, even on a `Data` that wasn't already unscrubbed.
+        let data: Data = data_region(64, 0).into();
+        let unscrubbed: GrowableData<0, 0, false> = data.try_into().unwrap();
+        assert_eq!(unscrubbed.as_ref(), &data_region(64, 0));
+
+        // SECRET = true: turns scrubbing back on, even on a `Data` that was already unscrubbed.
+        let unscrubbed_data: Data = Data::from(data_region(64, 0)).into_unscrubbed();
+        let resecreted: GrowableData<0, 0, true> = unscrubbed_data.try_into().unwrap();
+        assert_eq!(resecreted.as_ref(), &data_region(64, 0));
+    }
 }
@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod data;
+mod growable_data;
+
+pub use data::Data;
+pub use growable_data::GrowableData;